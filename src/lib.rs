@@ -1,9 +1,11 @@
 extern crate chrono;
 extern crate ytnef_sys;
 extern crate lazy_static;
+extern crate encoding_rs;
 
 pub mod tnef;
 pub mod mapi;
+mod rtf;
 mod utils;
 
 pub use self::tnef::*;
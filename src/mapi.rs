@@ -1,5 +1,78 @@
+use std::convert::TryInto;
+use chrono::NaiveDateTime;
 use crate::utils::*;
 
+/// Property type codes from the low 16 bits of a MAPI property tag.
+/// See `MAPIProperty::value` for the supported subset.
+const PT_LONG: u16     = 0x0003;
+const PT_BOOLEAN: u16  = 0x000B;
+const PT_I8: u16       = 0x0014;
+const PT_STRING8: u16  = 0x001E;
+const PT_UNICODE: u16  = 0x001F;
+const PT_SYSTIME: u16  = 0x0040;
+const PT_BINARY: u16   = 0x0102;
+const PT_MV_FLAG: u16  = 0x1000;
+
+/// A decoded MAPI property value. See `MAPIProperty::value`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MAPIValue {
+	Long(i32),
+	Boolean(bool),
+	I8(i64),
+	String(String),
+	SysTime(NaiveDateTime),
+	Binary(Vec<u8>),
+	MultiValue(Vec<MAPIValue>)
+}
+
+/// Decode a single (non-multivalue) variableLength according to `prop_type`,
+/// which must already have the `PT_MV_FLAG` bit stripped. `encoding` is used
+/// to decode `PT_STRING8` text, the same way `TNEFFile`'s string accessors
+/// decode legacy code pages instead of assuming UTF-8.
+fn decode_value(
+	prop_type: u16,
+	raw: ytnef_sys::variableLength,
+	encoding: Option<&'static encoding_rs::Encoding>
+) -> Option<MAPIValue> {
+	match prop_type {
+		PT_LONG => {
+			let bytes = vec_from_varlen(raw)?;
+			let array: [u8; 4] = bytes.get(0..4)?.try_into().ok()?;
+			Some(MAPIValue::Long(i32::from_le_bytes(array)))
+		},
+		PT_BOOLEAN => {
+			let bytes = vec_from_varlen(raw)?;
+			let array: [u8; 2] = bytes.get(0..2)?.try_into().ok()?;
+			Some(MAPIValue::Boolean(u16::from_le_bytes(array) != 0))
+		},
+		PT_I8 => {
+			let bytes = vec_from_varlen(raw)?;
+			let array: [u8; 8] = bytes.get(0..8)?.try_into().ok()?;
+			Some(MAPIValue::I8(i64::from_le_bytes(array)))
+		},
+		PT_STRING8 => {
+			Some(MAPIValue::String(string_from_varlen_with_encoding(raw, encoding)?))
+		},
+		PT_UNICODE => {
+			let bytes = vec_from_varlen(raw)?;
+			let units: Vec<u16> = bytes
+				.chunks_exact(2)
+				.map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+				.collect();
+			Some(MAPIValue::String(String::from_utf16(&units).ok()?))
+		},
+		PT_SYSTIME => {
+			let bytes = vec_from_varlen(raw)?;
+			let array: [u8; 8] = bytes.get(0..8)?.try_into().ok()?;
+			Some(MAPIValue::SysTime(datetime_from_filetime(u64::from_le_bytes(array))))
+		},
+		PT_BINARY => {
+			Some(MAPIValue::Binary(vec_from_varlen(raw)?))
+		},
+		_ => None
+	}
+}
+
 pub struct MAPIProperty {
 	inner: ytnef_sys::MAPIProperty
 }
@@ -55,4 +128,48 @@ impl MAPIProperty {
 	pub fn data(&self) -> Option<Vec<u8>> {
 		vec_from_varlen(unsafe { self.inner.data.read() })
 	}
+
+	/// The property id, i.e. the high 16 bits of the 32-bit property tag
+	/// held in `id()`.
+	pub fn prop_id(&self) -> u16 {
+		(self.inner.id >> 16) as u16
+	}
+
+	/// The property type code, i.e. the low 16 bits of the 32-bit property
+	/// tag held in `id()`. Includes the `PT_MV_FLAG` bit for multivalue
+	/// properties.
+	pub fn prop_type(&self) -> u16 {
+		(self.inner.id & 0xFFFF) as u16
+	}
+
+	/// Decode this property's raw bytes according to its type code, turning
+	/// the opaque `data()` blob into a typed value. `PT_STRING8` text is
+	/// decoded as lossy UTF-8; use `value_with_encoding` to decode it with a
+	/// message's resolved code page instead. Returns `None` for unsupported
+	/// or malformed property types.
+	pub fn value(&self) -> Option<MAPIValue> {
+		self.value_with_encoding(None)
+	}
+
+	/// Like `value`, but decodes `PT_STRING8` text through `encoding` instead
+	/// of assuming UTF-8. Pass `TNEFFile::encoding()` here when decoding
+	/// properties fetched via `TNEFFile::mapi_properties()`/`find_property()`,
+	/// the same way `subject()`, `from()`, `body()`, etc. decode their text.
+	pub fn value_with_encoding(&self, encoding: Option<&'static encoding_rs::Encoding>) -> Option<MAPIValue> {
+		let prop_type = self.prop_type();
+		let base_type = prop_type & !PT_MV_FLAG;
+
+		if prop_type & PT_MV_FLAG != 0 {
+			let mut values: Vec<MAPIValue> = vec![];
+
+			for i in 0..self.inner.count {
+				let raw = unsafe { *self.inner.data.offset(i as isize) };
+				values.push(decode_value(base_type, raw, encoding)?);
+			}
+
+			Some(MAPIValue::MultiValue(values))
+		} else {
+			decode_value(base_type, unsafe { self.inner.data.read() }, encoding)
+		}
+	}
 }
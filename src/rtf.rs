@@ -0,0 +1,174 @@
+//! Decompressor for the LZFu-compressed RTF bodies carried in the
+//! `PR_RTF_COMPRESSED` MAPI property.
+
+use std::convert::TryInto;
+
+/// Fixed RTF dictionary prebuffer every LZFu stream is primed with, per the
+/// TNEF/compressed-RTF spec.
+const PREBUFFER: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+const DICT_SIZE: usize = 4096;
+const MAGIC_COMPRESSED: u32 = 0x75465A4C;   // "LZFu"
+const MAGIC_UNCOMPRESSED: u32 = 0x414C454D; // "MELA"
+
+/// Hard upper bound on a decompressed RTF body. Guards against a crafted
+/// RAWSIZE header value driving an unconditional multi-gigabyte allocation
+/// before a single byte has actually been decoded.
+const MAX_RAW_SIZE: usize = 16 * 1024 * 1024;
+
+/// Decompress a `PR_RTF_COMPRESSED` blob (16-byte header followed by either
+/// an LZFu-compressed or raw RTF stream) into its RTF text.
+pub fn decompress(data: &[u8]) -> Option<String> {
+	if data.len() < 16 {
+		return None;
+	}
+
+	let comp_size = u32::from_le_bytes(data[0..4].try_into().ok()?) as usize;
+	let raw_size = (u32::from_le_bytes(data[4..8].try_into().ok()?) as usize).min(MAX_RAW_SIZE);
+	let comp_type = u32::from_le_bytes(data[8..12].try_into().ok()?);
+
+	// COMPSIZE is the size of everything after itself (RAWSIZE, COMPTYPE, CRC
+	// and the body). A malformed/truncated COMPSIZE means we can't trust the
+	// property actually holds the body it claims to.
+	if comp_size > data.len() - 4 {
+		return None;
+	}
+
+	let body = &data[16..];
+
+	let decompressed = match comp_type {
+		MAGIC_UNCOMPRESSED => body.get(..raw_size)?.to_vec(),
+		MAGIC_COMPRESSED => decompress_lzfu(body, raw_size),
+		_ => return None
+	};
+
+	Some(String::from_utf8_lossy(&decompressed).into_owned())
+}
+
+/// Run the LZFu back-reference algorithm over `body`, priming the 4096-byte
+/// circular dictionary with `PREBUFFER` as the spec requires.
+fn decompress_lzfu(body: &[u8], raw_size: usize) -> Vec<u8> {
+	let mut dict = [0u8; DICT_SIZE];
+	dict[..PREBUFFER.len()].copy_from_slice(PREBUFFER);
+
+	let mut cursor = PREBUFFER.len() % DICT_SIZE;
+	let mut output: Vec<u8> = Vec::with_capacity(raw_size);
+	let mut pos = 0usize;
+
+	'outer: while pos < body.len() && output.len() < raw_size {
+		let control = body[pos];
+		pos += 1;
+
+		for bit in 0..8 {
+			if output.len() >= raw_size {
+				break 'outer;
+			}
+
+			if (control >> bit) & 1 == 0 {
+				// literal byte: copy to output and the dictionary
+				let byte = match body.get(pos) {
+					Some(&byte) => byte,
+					None => break 'outer
+				};
+				pos += 1;
+
+				dict[cursor] = byte;
+				cursor = (cursor + 1) % DICT_SIZE;
+				output.push(byte);
+			} else {
+				// back-reference token: 12-bit dictionary offset, 4-bit (length - 2)
+				if pos + 2 > body.len() {
+					break 'outer;
+				}
+				let token = u16::from_be_bytes([body[pos], body[pos + 1]]);
+				pos += 2;
+
+				let offset = (token >> 4) as usize;
+				let length = (token & 0xF) as usize + 2;
+
+				if offset == cursor {
+					break 'outer;
+				}
+
+				let mut read_cursor = offset;
+				for _ in 0..length {
+					if output.len() >= raw_size {
+						break;
+					}
+
+					let byte = dict[read_cursor];
+					dict[cursor] = byte;
+					output.push(byte);
+					cursor = (cursor + 1) % DICT_SIZE;
+					read_cursor = (read_cursor + 1) % DICT_SIZE;
+				}
+			}
+		}
+	}
+
+	output
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn decompress_lzfu_literal_run() {
+		// control byte 0x00: every bit in this byte is a literal
+		let body = [0x00, b'H', b'i'];
+		assert_eq!(decompress_lzfu(&body, 2), b"Hi");
+	}
+
+	#[test]
+	fn decompress_lzfu_back_reference() {
+		// write "AB" as literals, then a back-reference token pointing at
+		// "AB" with length 4 - an overlapping LZ77 copy that repeats the
+		// two bytes just written to produce "ABAB"
+		let cursor0 = PREBUFFER.len() % DICT_SIZE;
+		let token: u16 = ((cursor0 as u16) << 4) | 2; // offset=cursor0, length-2=2
+		let mut body = vec![0b0000_0100, b'A', b'B'];
+		body.extend_from_slice(&token.to_be_bytes());
+
+		assert_eq!(decompress_lzfu(&body, 6), b"ABABAB");
+	}
+
+	#[test]
+	fn decompress_lzfu_stops_at_self_referential_offset() {
+		// a token whose offset equals the current cursor is the end
+		// sentinel and must terminate decoding rather than reading garbage
+		let cursor0 = PREBUFFER.len() % DICT_SIZE;
+		let token: u16 = (cursor0 as u16) << 4;
+		let mut body = vec![0b0000_0001];
+		body.extend_from_slice(&token.to_be_bytes());
+
+		assert!(decompress_lzfu(&body, 100).is_empty());
+	}
+
+	#[test]
+	fn decompress_full_header_roundtrip() {
+		let mut data = vec![0u8; 16];
+		data[4..8].copy_from_slice(&2u32.to_le_bytes()); // RAWSIZE
+		data[8..12].copy_from_slice(&MAGIC_COMPRESSED.to_le_bytes());
+		data.push(0x00); // control byte: literal run
+		data.push(b'H');
+		data.push(b'i');
+
+		assert_eq!(decompress(&data), Some("Hi".to_string()));
+	}
+
+	#[test]
+	fn decompress_rejects_short_header() {
+		assert_eq!(decompress(&[0u8; 8]), None);
+	}
+
+	#[test]
+	fn decompress_rejects_comp_size_past_end_of_data() {
+		let mut data = vec![0u8; 16];
+		// claim far more data follows than we actually supply
+		data[0..4].copy_from_slice(&1_000_000u32.to_le_bytes());
+		data[8..12].copy_from_slice(&MAGIC_COMPRESSED.to_le_bytes());
+
+		assert_eq!(decompress(&data), None);
+	}
+}
@@ -1,26 +1,32 @@
 
 use std::fmt;
-use std::io::Read;
+use std::fs::File;
+use std::io::{Read, Write};
 use std::ops::Drop;
 use std::ffi::CString;
-use std::convert::From;
+use std::convert::{From, TryInto};
 use std::mem::MaybeUninit;
 use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::collections::HashSet;
 use chrono::NaiveDateTime;
 use crate::mapi::MAPIProperty;
 use crate::utils::*;
 
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug)]
 pub enum TNEFError {
-	CannotInitData   = -1,
-	NotTnefStream    = -2,
-	ErrorReadingData = -3,
-	NoKey            = -4,
-	BadChecksum      = -5,
-	ErrorInHandler   = -6,
-	UnknownProperty  = -7,
-	IncorrectSetup   = -8,
-	UnknownError     = -9
+	CannotInitData,
+	NotTnefStream,
+	ErrorReadingData,
+	NoKey,
+	BadChecksum,
+	ErrorInHandler,
+	UnknownProperty,
+	IncorrectSetup,
+	UnknownError,
+	/// Raised when the underlying `Read` supplied to `TNEFFile::new` returns
+	/// an error, rather than letting it collapse into `ErrorReadingData`.
+	Io(std::io::Error)
 }
 
 /// Used for conveniently converting return values from ytnef_sys into an error type
@@ -42,18 +48,28 @@ impl From<i32> for TNEFError {
 
 impl fmt::Display for TNEFError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		let msg = match self {
-			TNEFError::CannotInitData   => "Cannot initialize data",
-			TNEFError::NotTnefStream    => "Not a TNEF stream",
-			TNEFError::ErrorReadingData => "Error reading data",
-			TNEFError::NoKey            => "No key",
-			TNEFError::BadChecksum      => "Bad checksum",
-			TNEFError::ErrorInHandler   => "Error in I/O handler",
-			TNEFError::UnknownProperty  => "Unkown property",
-			TNEFError::IncorrectSetup   => "Incorrect setup",
-			_ => "Unkown error"
+		let (msg, code): (&str, i32) = match self {
+			TNEFError::CannotInitData   => ("Cannot initialize data", -1),
+			TNEFError::NotTnefStream    => ("Not a TNEF stream", -2),
+			TNEFError::ErrorReadingData => ("Error reading data", -3),
+			TNEFError::NoKey            => ("No key", -4),
+			TNEFError::BadChecksum      => ("Bad checksum", -5),
+			TNEFError::ErrorInHandler   => ("Error in I/O handler", -6),
+			TNEFError::UnknownProperty  => ("Unkown property", -7),
+			TNEFError::IncorrectSetup   => ("Incorrect setup", -8),
+			TNEFError::UnknownError     => ("Unkown error", -9),
+			TNEFError::Io(err) => return write!(f, "I/O error: {}", err)
 		};
-		write!(f, "{} ({})", msg, *self as u8)
+		write!(f, "{} ({})", msg, code)
+	}
+}
+
+impl std::error::Error for TNEFError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			TNEFError::Io(err) => Some(err),
+			_ => None
+		}
 	}
 }
 
@@ -64,7 +80,11 @@ pub struct TNEFFile {
 }
 
 struct ReaderWrapper {
-	inner: Box<dyn Read>
+	inner: Box<dyn Read>,
+	/// Populated by `tnef_io_read` if the inner reader ever returns an
+	/// `io::Error`, so `TNEFFile::new` can surface the real cause instead of
+	/// the generic `ErrorReadingData` that ytnef_sys reports for a `-1` read.
+	error: Option<std::io::Error>
 }
 
 unsafe extern "C" fn tnef_io_open(_io: *mut ytnef_sys::_TNEFIOStruct) -> c_int {
@@ -80,21 +100,33 @@ unsafe extern "C" fn tnef_io_read(
 	// extract our reader from the `data' field in our I/O struct
 	let mut reader = Box::from_raw((*io).data as *mut ReaderWrapper);
 
-	// allocate a buffer sufficient for the amount of data we will read
-	let buffer_size: usize = (size * count) as usize;
-	let mut buffer: Vec<u8> = vec![0; buffer_size];
+	// allocate a buffer sufficient for the amount of data requested, then
+	// loop on the inner reader until it is full or we hit EOF, mirroring
+	// fread's element-count semantics
+	let elem_size: usize = size as usize;
+	let total: usize = elem_size * count as usize;
+	let mut buffer: Vec<u8> = vec![0; total];
+	let mut filled: usize = 0;
+
+	while filled < total {
+		match reader.inner.read(&mut buffer[filled..]) {
+			Ok(0) => break, // EOF
+			Ok(bytes_read) => filled += bytes_read,
+			Err(err) => {
+				reader.error = Some(err);
+				(*io).data = Box::into_raw(reader) as *mut c_void;
+				return -1;
+			}
+		}
+	}
 
-	// read data from our reader and write data to the `dest' buffer
-	let bytes_read: i32 = match reader.inner.read(&mut buffer) {
-		Ok(bytes_read) => bytes_read as i32,
-		Err(_) => -1
-	};
-	buffer.as_ptr().copy_to(dest as *mut u8, buffer_size);
+	// only copy the bytes we actually read into `dest'
+	buffer.as_ptr().copy_to(dest as *mut u8, filled);
 
 	// turn our box back into a raw pointer to avoid double free then
-	// return our result
+	// return the number of complete elements read
 	(*io).data = Box::into_raw(reader) as *mut c_void;
-	bytes_read
+	if elem_size == 0 { 0 } else { (filled / elem_size) as i32 }
 }
 
 unsafe extern "C" fn tnef_io_close(_io: *mut ytnef_sys::_TNEFIOStruct) -> c_int {
@@ -104,18 +136,19 @@ unsafe extern "C" fn tnef_io_close(_io: *mut ytnef_sys::_TNEFIOStruct) -> c_int
 impl TNEFFile {
 	// impl with Read trait instead
 	pub fn new<R: 'static + Read>(reader: R) -> TNEFResult<Self> {
-		let reader_wrapper = Box::new(ReaderWrapper {
-			inner: Box::new(reader)
-		});
+		let reader_ptr = Box::into_raw(Box::new(ReaderWrapper {
+			inner: Box::new(reader),
+			error: None
+		}));
 
 		// configure IO struct
 		let io = ytnef_sys::_TNEFIOStruct {
 			InitProc: Some(tnef_io_open),
 			ReadProc: Some(tnef_io_read),
 			CloseProc: Some(tnef_io_close),
-			data: Box::into_raw(reader_wrapper) as *mut c_void
+			data: reader_ptr as *mut c_void
 		};
-		
+
 		// initialize TNEF struct
 		let mut inner = MaybeUninit::<ytnef_sys::TNEFStruct>::zeroed();
 
@@ -127,6 +160,13 @@ impl TNEFFile {
 			ytnef_sys::TNEFParse(inner_ptr)
 		};
 
+		// if our reader ever surfaced an io::Error, prefer that over the
+		// generic ErrorReadingData ytnef_sys reports for a failed read
+		let io_error = unsafe { (*reader_ptr).error.take() };
+		if let Some(err) = io_error {
+			return Err(TNEFError::Io(err));
+		}
+
 		if result < 0 {
 			Err(result.into())
 		} else {
@@ -192,11 +232,11 @@ impl TNEFFile {
 	}
 
 	pub fn from(&self) -> Option<String> {
-		string_from_varlen(self.inner.from)
+		self.decode_text(self.inner.from)
 	}
 
 	pub fn subject(&self) -> Option<String> {
-		string_from_varlen(self.inner.subject)
+		self.decode_text(self.inner.subject)
 	}
 
 	pub fn date_sent(&self) -> NaiveDateTime {
@@ -242,7 +282,7 @@ impl TNEFFile {
 	}
 
 	pub fn body(&self) -> Option<String> {
-		string_from_varlen(self.inner.body)
+		self.decode_text(self.inner.body)
 	}
 
 	pub fn priority(&self) -> String {
@@ -282,29 +322,140 @@ impl TNEFFile {
 		output
 	}
 
+	/// Look up a MAPI property by its property id (the high 16 bits of the
+	/// property tag), ignoring the type bits.
+	///
+	/// Deliberately returns an owned `MAPIProperty` rather than a borrow:
+	/// `mapi_properties()` copies every `MAPIProperty` out of the underlying
+	/// C array on each call instead of caching them on `self`, so a `&`
+	/// returned from here could only ever borrow from that call's temporary
+	/// `Vec` and couldn't outlive this function - there's no `self`-owned
+	/// storage to borrow from.
+	pub fn find_property(&self, id: u16) -> Option<MAPIProperty> {
+		self.mapi_properties()
+			.into_iter()
+			.find(|prop| prop.prop_id() == id)
+	}
+
+	/// Locate the `PR_RTF_COMPRESSED` MAPI property and decompress it. TNEF
+	/// messages frequently carry their real body here (LZFu-compressed RTF)
+	/// rather than in `body()`.
+	pub fn rtf_body(&self) -> Option<String> {
+		const PR_RTF_COMPRESSED: u16 = 0x1009;
+		let data = self.find_property(PR_RTF_COMPRESSED)?.data()?;
+		crate::rtf::decompress(&data)
+	}
+
 	pub fn code_page(&self) -> Option<Vec<u8>> {
 		vec_from_varlen(self.inner.CodePage)
 	}
 
+	/// The text encoding resolved from this message's `CodePage` MAPI
+	/// property, used to decode `from()`, `subject()`, `body()`, and the
+	/// other string accessors below. Falls back to `"UTF-8"` when no code
+	/// page is present or it doesn't map to a known encoding.
+	pub fn charset(&self) -> String {
+		self.encoding()
+			.unwrap_or(encoding_rs::UTF_8)
+			.name()
+			.to_string()
+	}
+
+	/// The `encoding_rs` Encoding resolved from this message's `CodePage`
+	/// field, or `None` if it's absent or doesn't map to a known code page.
+	/// Exposed so callers decoding generic MAPI properties themselves (e.g.
+	/// via `mapi_properties()`/`find_property()`) can pass the same charset
+	/// into `MAPIProperty::value_with_encoding`.
+	pub fn encoding(&self) -> Option<&'static encoding_rs::Encoding> {
+		let bytes = self.code_page()?;
+		let array: [u8; 4] = bytes.get(0..4)?.try_into().ok()?;
+		encoding_from_code_page(u32::from_le_bytes(array))
+	}
+
+	fn decode_text(&self, attr: ytnef_sys::variableLength) -> Option<String> {
+		string_from_varlen_with_encoding(attr, self.encoding())
+	}
+
 	pub fn original_message_class(&self) -> Option<String> {
 		// FIXME: maybe make enum
-		string_from_varlen(self.inner.OriginalMessageClass)
+		self.decode_text(self.inner.OriginalMessageClass)
 	}
 
 	pub fn owner(&self) -> Option<String> {
-		string_from_varlen(self.inner.Owner)
+		self.decode_text(self.inner.Owner)
 	}
 
 	pub fn sent_for(&self) -> Option<String> {
-		string_from_varlen(self.inner.SentFor)
+		self.decode_text(self.inner.SentFor)
 	}
 
 	pub fn delegate(&self) -> Option<String> {
-		string_from_varlen(self.inner.Delegate)
+		self.decode_text(self.inner.Delegate)
 	}
 
 	pub fn aid_owner(&self) -> Option<String> {
-		string_from_varlen(self.inner.AidOwner)
+		self.decode_text(self.inner.AidOwner)
+	}
+
+	/// Save every attachment to `dir`, deriving each filename from
+	/// `transport_filename()` (falling back to `title()`, then a generated
+	/// `attachment-N.bin`). Filenames are sanitized to strip path separators
+	/// and `..` components so a malicious `winmail.dat` can't write outside
+	/// `dir`, and collisions are de-duplicated by appending a counter.
+	pub fn extract_all<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<Vec<PathBuf>> {
+		let dir = dir.as_ref();
+		let mut used_names: HashSet<String> = HashSet::new();
+		let mut output: Vec<PathBuf> = vec![];
+
+		for (i, attachment) in self.attachments().iter().enumerate() {
+			let base_name = attachment.transport_filename()
+				.or_else(|| attachment.title())
+				.map(|name| sanitize_filename(&name))
+				.filter(|name| !name.is_empty())
+				.unwrap_or_else(|| format!("attachment-{}.bin", i));
+
+			let name = dedupe_filename(&base_name, &mut used_names);
+			let path = dir.join(&name);
+
+			let mut file = File::create(&path)?;
+			attachment.write_to(&mut file)?;
+
+			output.push(path);
+		}
+
+		Ok(output)
+	}
+}
+
+/// Strip path separators and `..` components from an attachment-supplied
+/// filename so it can't escape the target directory in `extract_all`.
+fn sanitize_filename(name: &str) -> String {
+	name
+		.split(|c| c == '/' || c == '\\')
+		.filter(|part| !part.is_empty() && *part != "..")
+		.collect::<Vec<_>>()
+		.join("_")
+}
+
+/// Make `name` unique against `used`, appending `-1`, `-2`, etc. before the
+/// extension on collision.
+fn dedupe_filename(name: &str, used: &mut HashSet<String>) -> String {
+	if used.insert(name.to_string()) {
+		return name.to_string();
+	}
+
+	let (stem, ext) = match name.rfind('.') {
+		Some(idx) if idx > 0 => (&name[..idx], &name[idx..]),
+		_ => (name, "")
+	};
+
+	let mut counter = 1;
+	loop {
+		let candidate = format!("{}-{}{}", stem, counter, ext);
+		if used.insert(candidate.clone()) {
+			return candidate;
+		}
+		counter += 1;
 	}
 }
 
@@ -391,6 +542,14 @@ impl TNEFAttachment {
 	pub fn icon_data(&self) -> Option<Vec<u8>> {
 		vec_from_varlen(self.inner.IconData)
 	}
+
+	/// Write this attachment's decoded file data to `out`, returning the
+	/// number of bytes written.
+	pub fn write_to<W: Write>(&self, out: &mut W) -> std::io::Result<u64> {
+		let data = self.file_data().unwrap_or_default();
+		out.write_all(&data)?;
+		Ok(data.len() as u64)
+	}
 }
 
 #[cfg(test)]
@@ -414,4 +573,66 @@ mod test {
 		let mut buffer: Vec<u8> = read("test_data/winmail.dat").unwrap();
 		let _ = TNEFFile::from_buffer(&mut buffer).unwrap();
 	}
+
+	#[test]
+	fn mapi_properties_decode_typed_values() {
+		let file = TNEFFile::from_file("test_data/winmail.dat".to_string()).unwrap();
+		let properties = file.mapi_properties();
+
+		assert!(!properties.is_empty());
+
+		for property in &properties {
+			// decoding must never panic, even for property types this crate
+			// doesn't recognize - those just decode to None
+			let _ = property.value();
+		}
+
+		let first_id = properties[0].prop_id();
+		let found = file.find_property(first_id)
+			.expect("find_property should locate a property mapi_properties() already returned");
+		assert_eq!(found.prop_id(), first_id);
+	}
+
+	#[test]
+	fn charset_and_encoding_resolve_from_real_message() {
+		let file = TNEFFile::from_file("test_data/winmail.dat".to_string()).unwrap();
+
+		// charset() always resolves to a label, falling back to "UTF-8" when
+		// CodePage is absent or unrecognized
+		let charset = file.charset();
+		assert!(!charset.is_empty());
+
+		// encoding() and charset() must stay in sync: the resolved
+		// Encoding's name is exactly what charset() reports
+		let expected = file.encoding().unwrap_or(encoding_rs::UTF_8).name();
+		assert_eq!(charset, expected);
+	}
+
+	#[test]
+	fn sanitize_filename_strips_path_traversal() {
+		assert_eq!(sanitize_filename("../../etc/passwd"), "etc_passwd");
+		assert_eq!(sanitize_filename(".."), "");
+		assert_eq!(sanitize_filename("../.."), "");
+	}
+
+	#[test]
+	fn sanitize_filename_strips_separators() {
+		assert_eq!(sanitize_filename("a/b\\c"), "a_b_c");
+		assert_eq!(sanitize_filename("report.docx"), "report.docx");
+	}
+
+	#[test]
+	fn dedupe_filename_is_noop_for_first_use() {
+		let mut used = HashSet::new();
+		assert_eq!(dedupe_filename("a.txt", &mut used), "a.txt");
+	}
+
+	#[test]
+	fn dedupe_filename_appends_counter_on_collision() {
+		let mut used = HashSet::new();
+		used.insert("a.txt".to_string());
+
+		assert_eq!(dedupe_filename("a.txt", &mut used), "a-1.txt");
+		assert_eq!(dedupe_filename("a.txt", &mut used), "a-2.txt");
+	}
 }
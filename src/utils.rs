@@ -34,6 +34,60 @@ pub fn string_from_varlen(
 	String::from_utf8(vec_from_varlen(attr)?).ok()
 }
 
+/// Map a numeric Windows code page (from the `PR_MESSAGE_CODEPAGE` MAPI
+/// property) to its `encoding_rs` Encoding. Returns `None` for an
+/// unrecognized code page, so callers can fall back to lossy UTF-8.
+pub fn encoding_from_code_page(codepage: u32) -> Option<&'static encoding_rs::Encoding> {
+	use encoding_rs::*;
+
+	Some(match codepage {
+		874   => WINDOWS_874,
+		932   => SHIFT_JIS,
+		936   => GBK,
+		949   => EUC_KR,
+		950   => BIG5,
+		1200  => UTF_16LE,
+		1201  => UTF_16BE,
+		1250  => WINDOWS_1250,
+		1251  => WINDOWS_1251,
+		1252  => WINDOWS_1252,
+		1253  => WINDOWS_1253,
+		1254  => WINDOWS_1254,
+		1255  => WINDOWS_1255,
+		1256  => WINDOWS_1256,
+		1257  => WINDOWS_1257,
+		1258  => WINDOWS_1258,
+		20866 => KOI8_R,
+		65001 => UTF_8,
+		_ => return None
+	})
+}
+
+/// Decode a variableLength field as text using `encoding` when given,
+/// falling back to lossy UTF-8 otherwise - so legacy code pages decode
+/// correctly instead of silently becoming `None` on invalid UTF-8.
+pub fn string_from_varlen_with_encoding(
+	attr: ytnef_sys::variableLength,
+	encoding: Option<&'static encoding_rs::Encoding>
+) -> Option<String> {
+	let bytes = vec_from_varlen(attr)?;
+
+	Some(match encoding {
+		Some(encoding) => encoding.decode(&bytes).0.into_owned(),
+		None => String::from_utf8_lossy(&bytes).into_owned()
+	})
+}
+
+/// Convert a Windows FILETIME (100-ns ticks since 1601-01-01) to
+/// chrono::NaiveDateTime, as used by PT_SYSTIME MAPI properties.
+pub fn datetime_from_filetime(ticks: u64) -> NaiveDateTime {
+	let secs_since_1601 = ticks / 10_000_000;
+	let nanos = (ticks % 10_000_000) * 100;
+	let unix_secs = secs_since_1601 as i64 - 11_644_473_600;
+
+	NaiveDateTime::from_timestamp(unix_secs, nanos as u32)
+}
+
 /// Conveniently convertthe dtr native type fro ytnef to chrono::NaiveDateTime
 pub fn datetime_from_dtr(date: ytnef_sys::dtr) -> NaiveDateTime {
 	let ndate = NaiveDate::from_ymd(